@@ -0,0 +1,116 @@
+use super::{Metrics, Protocol, SharedRegistry};
+use linkerd2_http_classify::ClassifyResponse;
+use std::future::Future;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::layer::Layer as _Layer;
+
+/// Wraps an inner service, recording each response into the `Requests<T,
+/// C>` registry it was built from.
+///
+/// Like `requests::tap::Service`, this never classifies anything itself:
+/// `T` is read from the request's extensions and `C` -- `L::Class` -- from
+/// the response's, both stashed there by earlier layers in the stack. `L`
+/// only anchors which `Class` type the registry is keyed by.
+pub struct Layer<T, L: ClassifyResponse> {
+    registry: SharedRegistry<T, L::Class>,
+    _marker: PhantomData<fn(L)>,
+}
+
+impl<T, L: ClassifyResponse> Layer<T, L> {
+    pub(super) fn new(registry: SharedRegistry<T, L::Class>) -> Self {
+        Self {
+            registry,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, L: ClassifyResponse> Clone for Layer<T, L> {
+    fn clone(&self) -> Self {
+        Self {
+            registry: self.registry.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, L: ClassifyResponse, S> _Layer<S> for Layer<T, L> {
+    type Service = Service<T, L, S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            registry: self.registry.clone(),
+            inner,
+            _marker: PhantomData,
+        }
+    }
+}
+
+pub struct Service<T, L: ClassifyResponse, S> {
+    registry: SharedRegistry<T, L::Class>,
+    inner: S,
+    _marker: PhantomData<fn(L)>,
+}
+
+impl<T, L: ClassifyResponse, S: Clone> Clone for Service<T, L, S> {
+    fn clone(&self) -> Self {
+        Self {
+            registry: self.registry.clone(),
+            inner: self.inner.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, L, S, ReqB, RspB> tower::Service<http::Request<ReqB>> for Service<T, L, S>
+where
+    T: Clone + Hash + Eq + Send + Sync + 'static,
+    L: ClassifyResponse + Send + Sync + 'static,
+    L::Class: Hash + Eq + Clone + Send + Sync + 'static,
+    S: tower::Service<http::Request<ReqB>, Response = http::Response<RspB>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqB>) -> Self::Future {
+        // Stashed by an earlier layer, the same convention
+        // `requests::tap::Inspect` uses to pull endpoint metadata out of a
+        // request this middleware didn't construct.
+        let target = req.extensions().get::<T>().cloned();
+        let protocol = Protocol::from_version(req.version());
+        let registry = self.registry.clone();
+        let start = Instant::now();
+
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let res = fut.await;
+
+            // Nothing to bucket this response by if either piece of
+            // context earlier layers are expected to stash is missing.
+            if let (Some(target), Ok(rsp)) = (target, &res) {
+                if let Some(class) = rsp.extensions().get::<L::Class>().cloned() {
+                    let status = Some(rsp.status());
+                    let mut registry = registry.write().unwrap();
+                    registry
+                        .by_target
+                        .entry(target)
+                        .or_insert_with(Metrics::default)
+                        .record(protocol, status, start.elapsed(), class);
+                }
+            }
+
+            res
+        })
+    }
+}
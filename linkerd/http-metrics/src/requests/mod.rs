@@ -2,8 +2,9 @@ use super::{LastUpdate, Registry, Report};
 use http;
 use indexmap::IndexMap;
 use linkerd2_http_classify::ClassifyResponse;
-use linkerd2_metrics::{latency, Counter, FmtMetrics, Histogram};
+use linkerd2_metrics::{latency, Counter, FmtLabels, FmtMetrics, Histogram};
 // use parking_lot::RwLock;
+use std::fmt;
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::sync::{Arc, Mutex, RwLock};
@@ -11,6 +12,7 @@ use std::time::{Duration, Instant};
 
 mod layer;
 mod report;
+pub mod tap;
 
 type SharedRegistry<T, C> = Arc<RwLock<Registry<T, Metrics<C>>>>;
 
@@ -20,6 +22,15 @@ where
     T: Hash + Eq,
     C: Hash + Eq;
 
+/// The wire protocol negotiated for a request, as observed from ALPN or the
+/// connection's `http::Version`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Http1,
+    H2,
+    H3,
+}
+
 #[derive(Debug)]
 pub struct Metrics<C>
 where
@@ -27,7 +38,11 @@ where
 {
     last_update: Instant,
     total: Counter,
-    by_status: IndexMap<Option<http::StatusCode>, StatusMetrics<C>>,
+    // Keyed by `(Protocol, status)` rather than just `status` so a single
+    // target's h1 and h2/h3 traffic get distinct latency/class buckets; see
+    // `record` (called from `layer.rs`) and the `protocol` label `report.rs`
+    // emits alongside it.
+    by_status: IndexMap<(Protocol, Option<http::StatusCode>), StatusMetrics<C>>,
 }
 
 #[derive(Debug)]
@@ -74,6 +89,33 @@ impl<T: Hash + Eq, C: Hash + Eq> Clone for Requests<T, C> {
     }
 }
 
+// === impl Protocol ===
+
+impl Protocol {
+    /// Determines the protocol from a request's negotiated HTTP version.
+    ///
+    /// Callers that negotiate HTTP/3 over QUIC via ALPN without it showing
+    /// up as `http::Version::HTTP_3` should record `Protocol::H3` directly
+    /// instead of deriving it from the request.
+    pub fn from_version(version: http::Version) -> Self {
+        match version {
+            http::Version::HTTP_2 => Protocol::H2,
+            http::Version::HTTP_3 => Protocol::H3,
+            _ => Protocol::Http1,
+        }
+    }
+}
+
+impl FmtLabels for Protocol {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Protocol::Http1 => write!(f, "protocol=\"h1\""),
+            Protocol::H2 => write!(f, "protocol=\"h2\""),
+            Protocol::H3 => write!(f, "protocol=\"h3\""),
+        }
+    }
+}
+
 // === impl Metrics ===
 
 impl<C: Hash + Eq> Default for Metrics<C> {
@@ -92,6 +134,35 @@ impl<C: Hash + Eq> LastUpdate for Metrics<C> {
     }
 }
 
+impl<C: Hash + Eq> Metrics<C> {
+    /// Records a completed request, bucketing it by `protocol` and `status`
+    /// and then by `class`. Called from `layer::Service::call` once a
+    /// response has been classified, rather than indexing `by_status`
+    /// directly.
+    pub(crate) fn record(
+        &mut self,
+        protocol: Protocol,
+        status: Option<http::StatusCode>,
+        latency: Duration,
+        class: C,
+    ) {
+        self.last_update = Instant::now();
+        self.total.incr();
+
+        let status_metrics = self
+            .by_status
+            .entry((protocol, status))
+            .or_insert_with(StatusMetrics::default);
+        status_metrics.latency.add(latency.into());
+        status_metrics
+            .by_class
+            .entry(class)
+            .or_insert_with(ClassMetrics::default)
+            .total
+            .incr();
+    }
+}
+
 impl<C> Default for StatusMetrics<C>
 where
     C: Hash + Eq,
@@ -0,0 +1,54 @@
+use super::Metrics;
+use crate::Report;
+use linkerd2_metrics::FmtLabels;
+use std::fmt;
+use std::hash::Hash;
+
+/// Renders `Requests` metrics in the Prometheus exposition format, scoped
+/// by target, then by the `(Protocol, status)` pair `Metrics::record`
+/// buckets by -- which is where the `protocol="h1"|"h2"|"h3"` label added
+/// alongside `Metrics::record` actually gets printed -- then by class.
+impl<T, C> linkerd2_metrics::FmtMetrics for Report<T, Metrics<C>>
+where
+    T: Hash + Eq + FmtLabels,
+    C: Hash + Eq + FmtLabels,
+{
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let registry = match self.registry.read() {
+            Ok(r) => r,
+            Err(_) => return Ok(()),
+        };
+        if registry.by_target.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(f, "# HELP request_total Total count of HTTP requests.")?;
+        writeln!(f, "# TYPE request_total counter")?;
+        for (target, metrics) in registry.by_target.iter() {
+            write!(f, "request_total{{")?;
+            target.fmt_labels(f)?;
+            writeln!(f, "}} {}", metrics.total)?;
+        }
+
+        writeln!(f, "# HELP response_total Total count of HTTP responses.")?;
+        writeln!(f, "# TYPE response_total counter")?;
+        for (target, metrics) in registry.by_target.iter() {
+            for ((protocol, status), status_metrics) in metrics.by_status.iter() {
+                for (class, class_metrics) in status_metrics.by_class.iter() {
+                    write!(f, "response_total{{")?;
+                    target.fmt_labels(f)?;
+                    write!(f, ",")?;
+                    protocol.fmt_labels(f)?;
+                    if let Some(status) = status {
+                        write!(f, ",status_code=\"{}\"", status.as_u16())?;
+                    }
+                    write!(f, ",")?;
+                    class.fmt_labels(f)?;
+                    writeln!(f, "}} {}", class_metrics.total)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,397 @@
+use http;
+use indexmap::IndexMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+use tower::layer::Layer as _Layer;
+
+/// Exposes per-request endpoint metadata (addresses, TLS status, destination
+/// labels) so that tap predicates can be evaluated without coupling this
+/// middleware to any particular stack's target type.
+///
+/// Implementations pull this metadata out of the request's extensions,
+/// where it was stashed by earlier layers in the stack.
+pub trait Inspect {
+    fn src_addr<B>(&self, req: &http::Request<B>) -> Option<SocketAddr>;
+    fn src_tls<B>(&self, req: &http::Request<B>) -> TlsStatus;
+    fn dst_addr<B>(&self, req: &http::Request<B>) -> Option<SocketAddr>;
+    fn dst_labels<B>(&self, req: &http::Request<B>) -> Option<&IndexMap<String, String>>;
+    fn dst_tls<B>(&self, req: &http::Request<B>) -> TlsStatus;
+}
+
+/// The negotiated TLS status of a connection endpoint.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TlsStatus {
+    Some,
+    None(&'static str),
+}
+
+/// A predicate describing the requests a tap subscriber is interested in.
+///
+/// A `None`/empty field matches anything; every present field must match for
+/// a request to be considered tapped.
+#[derive(Clone, Debug, Default)]
+pub struct Match {
+    pub src: Option<SocketAddr>,
+    pub dst: Option<SocketAddr>,
+    pub dst_labels: IndexMap<String, String>,
+    pub method: Option<http::Method>,
+    pub path_prefix: Option<String>,
+}
+
+impl Match {
+    fn matches<I: Inspect, B>(&self, inspect: &I, req: &http::Request<B>) -> bool {
+        if let Some(src) = self.src {
+            if inspect.src_addr(req) != Some(src) {
+                return false;
+            }
+        }
+
+        if let Some(dst) = self.dst {
+            if inspect.dst_addr(req) != Some(dst) {
+                return false;
+            }
+        }
+
+        if !self.dst_labels.is_empty() {
+            let matches = inspect
+                .dst_labels(req)
+                .map(|labels| self.dst_labels.iter().all(|(k, v)| labels.get(k) == Some(v)))
+                .unwrap_or(false);
+            if !matches {
+                return false;
+            }
+        }
+
+        if let Some(ref method) = self.method {
+            if req.method() != method {
+                return false;
+            }
+        }
+
+        if let Some(ref prefix) = self.path_prefix {
+            if !req.uri().path().starts_with(prefix.as_str()) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// An event emitted for a request matching a registered tap.
+///
+/// Emitted in order: `RequestInit`, `RequestEnd`, then either `ResponseInit`
+/// followed by `ResponseEnd` on success, or `Failed` if the inner service
+/// errors before a response is produced. Exactly one of `ResponseEnd`/
+/// `Failed` is sent per matched request, so a subscriber never sees a
+/// dangling `RequestInit` with no terminal event.
+///
+/// `ResponseEnd` carries the class an earlier layer already stashed in the
+/// response's extensions (the same one the metrics layer records), rather
+/// than reclassifying the response; it's `None` if none was stashed.
+#[derive(Clone, Debug)]
+pub enum Event<C> {
+    RequestInit { method: http::Method, path: String },
+    RequestEnd,
+    ResponseInit { status: http::StatusCode },
+    ResponseEnd { status: http::StatusCode, class: Option<C> },
+    Failed,
+}
+
+/// A registered predicate and the channel its events are published on.
+///
+/// Opaque to callers outside this module: `Subscribe::snapshot` hands out
+/// `Arc<Tapped<C>>`s so a discovery source doesn't need its own type for
+/// "a live registration", but its fields stay private.
+pub struct Tapped<C> {
+    match_: Match,
+    tx: mpsc::UnboundedSender<Event<C>>,
+}
+
+/// A shared registry of active tap predicates.
+///
+/// Registering a predicate and taking a snapshot of the live predicates both
+/// lock the registry; checking whether any tap is registered is a single
+/// atomic load, so a request costs no lock in the common case where no taps
+/// are registered.
+pub struct Registry<C> {
+    active: Arc<AtomicUsize>,
+    taps: Arc<Mutex<Vec<Weak<Tapped<C>>>>>,
+}
+
+impl<C> Clone for Registry<C> {
+    fn clone(&self) -> Self {
+        Self {
+            active: self.active.clone(),
+            taps: self.taps.clone(),
+        }
+    }
+}
+
+impl<C> Default for Registry<C> {
+    fn default() -> Self {
+        Self {
+            active: Arc::new(AtomicUsize::new(0)),
+            taps: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl<C> Registry<C> {
+    /// Registers a new predicate, returning a `Stream` of matching events.
+    ///
+    /// The predicate stays active only as long as the returned stream is
+    /// held; dropping it (or the whole stream, e.g. because the subscriber
+    /// went away) removes the predicate with no further bookkeeping.
+    pub fn register(&self, match_: Match) -> TapStream<C> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let tapped = Arc::new(Tapped { match_, tx });
+        self.taps.lock().unwrap().push(Arc::downgrade(&tapped));
+        self.active.fetch_add(1, Ordering::Release);
+        TapStream {
+            _tapped: tapped,
+            active: self.active.clone(),
+            rx,
+        }
+    }
+}
+
+/// A source of currently-active tap predicates.
+///
+/// This abstracts over how taps are discovered --- typically a `Registry`
+/// shared with a gRPC tap server --- so that the per-request middleware does
+/// not need to know how subscriptions are created or torn down.
+pub trait Subscribe<C>: Clone {
+    /// Returns `true` if at least one predicate is registered.
+    ///
+    /// Must be cheap: this is checked on every request.
+    fn any_registered(&self) -> bool;
+
+    /// Returns a snapshot of the currently live predicates.
+    fn snapshot(&self) -> Vec<Arc<Tapped<C>>>;
+}
+
+impl<C> Subscribe<C> for Registry<C> {
+    fn any_registered(&self) -> bool {
+        self.active.load(Ordering::Acquire) > 0
+    }
+
+    fn snapshot(&self) -> Vec<Arc<Tapped<C>>> {
+        let mut taps = self.taps.lock().unwrap();
+        taps.retain(|w| w.strong_count() > 0);
+        taps.iter().filter_map(Weak::upgrade).collect()
+    }
+}
+
+/// A handle to a registered tap, yielding its events as they occur.
+///
+/// Dropping this stream (e.g. because the consumer disconnected) drops the
+/// last strong reference to the predicate, so the registry's next snapshot
+/// silently omits it; there's no id-keyed map to clean up by hand.
+pub struct TapStream<C> {
+    _tapped: Arc<Tapped<C>>,
+    active: Arc<AtomicUsize>,
+    rx: mpsc::UnboundedReceiver<Event<C>>,
+}
+
+impl<C> Drop for TapStream<C> {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<C> futures::Stream for TapStream<C> {
+    type Item = Event<C>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.rx).poll_recv(cx)
+    }
+}
+
+/// A middleware that emits tap events for requests matching a registered
+/// `Match`, without acquiring a lock or allocating an event when no taps are
+/// registered.
+#[derive(Clone, Debug)]
+pub struct Layer<D, I> {
+    discover: D,
+    inspect: I,
+}
+
+impl<D, I: Clone> Layer<D, I> {
+    pub fn new(discover: D, inspect: I) -> Self {
+        Self { discover, inspect }
+    }
+}
+
+impl<D: Clone, I: Clone, S> _Layer<S> for Layer<D, I> {
+    type Service = Service<D, I, S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service {
+            discover: self.discover.clone(),
+            inspect: self.inspect.clone(),
+            inner,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Service<D, I, S> {
+    discover: D,
+    inspect: I,
+    inner: S,
+}
+
+impl<D, I, S, ReqB, RspB, C> tower::Service<http::Request<ReqB>> for Service<D, I, S>
+where
+    D: Subscribe<C>,
+    I: Inspect + Clone,
+    S: tower::Service<http::Request<ReqB>, Response = http::Response<RspB>>,
+    S::Error: Into<linkerd2_error::Error>,
+    S::Future: Send + 'static,
+    // `C` is the class an earlier layer already stashed in the response
+    // extensions under its own type, the same one the metrics layer records
+    // (see `Metrics<C>` in the parent module) -- tap never classifies
+    // anything itself.
+    C: Clone + Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = linkerd2_error::Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<ReqB>) -> Self::Future {
+        // The hot path: a single atomic load, no lock, no event, when no
+        // taps are registered anywhere in the proxy.
+        if !self.discover.any_registered() {
+            let fut = self.inner.call(req);
+            return Box::pin(async move { fut.await.map_err(Into::into) });
+        }
+
+        let taps: Vec<_> = self
+            .discover
+            .snapshot()
+            .into_iter()
+            .filter(|t| t.match_.matches(&self.inspect, &req))
+            .collect();
+
+        if taps.is_empty() {
+            let fut = self.inner.call(req);
+            return Box::pin(async move { fut.await.map_err(Into::into) });
+        }
+
+        for t in &taps {
+            let _ = t.tx.send(Event::RequestInit {
+                method: req.method().clone(),
+                path: req.uri().path().to_string(),
+            });
+            let _ = t.tx.send(Event::RequestEnd);
+        }
+
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let res = fut.await.map_err(Into::into);
+            match &res {
+                Ok(rsp) => {
+                    let status = rsp.status();
+                    let class = rsp.extensions().get::<C>().cloned();
+                    for t in &taps {
+                        let _ = t.tx.send(Event::ResponseInit { status });
+                        let _ = t.tx.send(Event::ResponseEnd {
+                            status,
+                            class: class.clone(),
+                        });
+                    }
+                }
+                Err(_) => {
+                    // No response was ever produced, so there's nothing to
+                    // carry a status/class; still close out the tap so a
+                    // subscriber doesn't see a dangling `RequestInit`.
+                    for t in &taps {
+                        let _ = t.tx.send(Event::Failed);
+                    }
+                }
+            }
+            res
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct NoopInspect;
+    impl Inspect for NoopInspect {
+        fn src_addr<B>(&self, _: &http::Request<B>) -> Option<SocketAddr> {
+            None
+        }
+        fn src_tls<B>(&self, _: &http::Request<B>) -> TlsStatus {
+            TlsStatus::None("not provided")
+        }
+        fn dst_addr<B>(&self, _: &http::Request<B>) -> Option<SocketAddr> {
+            None
+        }
+        fn dst_labels<B>(&self, _: &http::Request<B>) -> Option<&IndexMap<String, String>> {
+            None
+        }
+        fn dst_tls<B>(&self, _: &http::Request<B>) -> TlsStatus {
+            TlsStatus::None("not provided")
+        }
+    }
+
+    #[test]
+    fn no_taps_is_cheap_and_matches_nothing() {
+        let registry = Registry::<()>::default();
+        assert!(!registry.any_registered());
+        assert!(registry.snapshot().is_empty());
+    }
+
+    #[tokio::test]
+    async fn registration_is_removed_when_stream_drops() {
+        let registry = Registry::<()>::default();
+        assert!(!registry.any_registered());
+
+        let stream = registry.register(Match::default());
+        assert!(registry.any_registered());
+        assert_eq!(registry.snapshot().len(), 1);
+
+        drop(stream);
+        assert!(!registry.any_registered());
+        assert!(registry.snapshot().is_empty());
+    }
+
+    #[tokio::test]
+    async fn matching_predicate_filters_by_path_prefix() {
+        let registry = Registry::<()>::default();
+        let m = Match {
+            path_prefix: Some("/api".into()),
+            ..Match::default()
+        };
+        let stream = registry.register(m);
+
+        let inspect = NoopInspect;
+        let req = http::Request::builder()
+            .uri("/api/widgets")
+            .body(())
+            .unwrap();
+        let taps = registry.snapshot();
+        assert!(taps[0].match_.matches(&inspect, &req));
+
+        let other = http::Request::builder().uri("/other").body(()).unwrap();
+        assert!(!taps[0].match_.matches(&inspect, &other));
+
+        drop(taps);
+        drop(stream); // keep the registration alive until here
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{Client, Strategy};
+use http_body::Body as HttpBody;
+use linkerd2_error::{Error, Recover};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Weak};
+use tokio::sync::{watch, Mutex};
+use tonic::{
+    self as grpc,
+    body::{Body, BoxBody},
+    client::GrpcService,
+};
+
+/// Shares a single upstream `strategy` stream across callers watching the
+/// same `SocketAddr`, instead of spawning a new gRPC stream and daemon task
+/// for every `watch` call.
+#[derive(Clone, Debug)]
+pub struct Cache<S, R> {
+    client: Client<S, R>,
+    shared: Arc<Mutex<HashMap<SocketAddr, Weak<Entry>>>>,
+}
+
+/// Keeps a watch's upstream daemon alive for as long as any `Watch` handed
+/// out for its address is still held.
+#[derive(Debug)]
+struct Entry {
+    rx: watch::Receiver<Strategy>,
+}
+
+/// A cache-backed handle to a strategy watch.
+///
+/// Derefs to the underlying `watch::Receiver`. Once every `Watch` handed out
+/// for an address has been dropped, its cache entry's last reference goes
+/// with it, so the address's daemon observes `tx.closed()` and tears down
+/// its gRPC stream --- there's no separate idle timer involved.
+#[derive(Debug)]
+pub struct Watch {
+    rx: watch::Receiver<Strategy>,
+    _entry: Arc<Entry>,
+}
+
+impl Deref for Watch {
+    type Target = watch::Receiver<Strategy>;
+    fn deref(&self) -> &Self::Target {
+        &self.rx
+    }
+}
+
+impl DerefMut for Watch {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.rx
+    }
+}
+
+impl<S, R> Cache<S, R> {
+    pub fn new(client: Client<S, R>) -> Self {
+        Self {
+            client,
+            shared: Default::default(),
+        }
+    }
+}
+
+impl<S, R> Cache<S, R>
+where
+    S: GrpcService<BoxBody> + Clone + Send + 'static,
+    S::ResponseBody: Send,
+    <S::ResponseBody as Body>::Data: Send,
+    <S::ResponseBody as HttpBody>::Error:
+        Into<Box<dyn std::error::Error + Send + Sync + 'static>> + Send,
+    S::Future: Send,
+    R: Recover<grpc::Status> + Clone + Send + 'static,
+    R::Backoff: Send + Unpin,
+{
+    /// Returns a watch for `addr`, reusing an already-running `Client::watch`
+    /// stream for the same address when one exists.
+    ///
+    /// The map is locked for the whole miss path, including the upstream
+    /// `Client::watch` call, so two concurrent misses for the same cold
+    /// address can't race to spawn duplicate streams.
+    pub async fn watch(&self, addr: SocketAddr) -> Result<Watch, Error> {
+        let mut shared = self.shared.lock().await;
+
+        if let Some(entry) = shared.get(&addr).and_then(Weak::upgrade) {
+            let rx = entry.rx.clone();
+            return Ok(Watch { rx, _entry: entry });
+        }
+
+        let mut client = self.client.clone();
+        let rx = client.watch(addr).await?;
+
+        let entry = Arc::new(Entry { rx: rx.clone() });
+        shared.insert(addr, Arc::downgrade(&entry));
+
+        Ok(Watch { rx, _entry: entry })
+    }
+
+    /// Drops cache entries whose last `Watch` has already gone away.
+    ///
+    /// This is pure bookkeeping, not a liveness check --- a dead entry is
+    /// just as correctly replaced the next time `watch` misses on its
+    /// address --- but pruning periodically keeps the map from growing
+    /// without bound across addresses nobody watches anymore.
+    pub async fn retain_live(&self) {
+        self.shared
+            .lock()
+            .await
+            .retain(|_, entry| entry.strong_count() > 0);
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// A cloneable tripwire handed to every daemon a `Client` spawns.
+///
+/// `Drain::signaled` resolves once the linked `DrainSignal::drain` has been
+/// called, regardless of how many `Drain` clones are outstanding. Dropping a
+/// `Drain` tells the `DrainSignal` that one fewer daemon is outstanding.
+#[derive(Debug)]
+pub struct Drain {
+    signal: watch::Receiver<bool>,
+    count: Count,
+}
+
+/// Mints a fresh `Drain` for each daemon a `Client` spawns.
+///
+/// Unlike a `Drain`, holding a `Source` does not count as an outstanding
+/// daemon, so a `Client` can keep one for its whole lifetime without
+/// blocking `DrainSignal::drain` from completing.
+#[derive(Clone, Debug)]
+pub struct Source {
+    signal: watch::Receiver<bool>,
+    count: Count,
+}
+
+/// The handle used to trigger a drain and wait for every daemon it was
+/// handed to (via a `Drain` minted from the paired `Source`) to exit.
+#[derive(Debug)]
+pub struct DrainSignal {
+    tx: watch::Sender<bool>,
+    live: watch::Receiver<usize>,
+}
+
+/// The number of outstanding `Drain`s, broadcast over a `watch` channel so
+/// `DrainSignal::drain` can wait for it to reach zero without racing a
+/// drop: the channel always reflects the latest count, so there's no
+/// window between checking it and awaiting the next change in which a
+/// decrement can be missed (unlike `Notify::notify_waiters`, which wakes
+/// only waiters already registered at the moment it's called).
+#[derive(Clone, Debug)]
+struct Count(Arc<Mutex<CountInner>>);
+
+#[derive(Debug)]
+struct CountInner {
+    n: usize,
+    tx: watch::Sender<usize>,
+}
+
+impl Count {
+    fn new(tx: watch::Sender<usize>) -> Self {
+        Self(Arc::new(Mutex::new(CountInner { n: 0, tx })))
+    }
+
+    fn incr(&self) {
+        let mut inner = self.0.lock().unwrap();
+        inner.n += 1;
+        let _ = inner.tx.broadcast(inner.n);
+    }
+
+    fn decr(&self) {
+        let mut inner = self.0.lock().unwrap();
+        inner.n -= 1;
+        let _ = inner.tx.broadcast(inner.n);
+    }
+}
+
+/// Creates a linked `DrainSignal`/`Source` pair.
+pub fn channel() -> (DrainSignal, Source) {
+    let (tx, rx) = watch::channel(false);
+    let (live_tx, live_rx) = watch::channel(0usize);
+    (
+        DrainSignal { tx, live: live_rx },
+        Source {
+            signal: rx,
+            count: Count::new(live_tx),
+        },
+    )
+}
+
+impl Source {
+    /// Mints a new `Drain`, counting it against the paired `DrainSignal`
+    /// until it's dropped.
+    pub fn subscribe(&self) -> Drain {
+        self.count.incr();
+        Drain {
+            signal: self.signal.clone(),
+            count: self.count.clone(),
+        }
+    }
+}
+
+impl Drain {
+    /// Resolves once the signal has fired.
+    pub async fn signaled(mut self) {
+        while let Some(draining) = self.signal.recv().await {
+            if draining {
+                return;
+            }
+        }
+    }
+}
+
+impl Clone for Drain {
+    fn clone(&self) -> Self {
+        self.count.incr();
+        Self {
+            signal: self.signal.clone(),
+            count: self.count.clone(),
+        }
+    }
+}
+
+impl Drop for Drain {
+    fn drop(&mut self) {
+        self.count.decr();
+    }
+}
+
+impl DrainSignal {
+    /// Signals every outstanding `Drain` to shut down and waits for all of
+    /// them to be dropped, i.e. for every daemon to have observed the
+    /// signal and exited.
+    pub async fn drain(mut self) {
+        let _ = self.tx.broadcast(true);
+
+        // `recv` always yields the latest broadcast count, starting with
+        // whatever it is right now, so there's no gap between an `incr`/
+        // `decr` and observing it here to race.
+        while let Some(n) = self.live.recv().await {
+            if n == 0 {
+                return;
+            }
+        }
+    }
+}
@@ -13,11 +13,43 @@ use tonic::{
 };
 use tracing::trace;
 
+mod cache;
+mod drain;
+
+pub use self::cache::Cache;
+pub use self::drain::{Drain, DrainSignal};
+use self::drain::Source;
+
 #[derive(Clone, Debug)]
 pub struct Client<S, R> {
     service: DestinationClient<S>,
     recover: R,
     context_token: String,
+    drain: Source,
+}
+
+impl<S, R> Client<S, R> {
+    /// Builds a `Client`, along with the `DrainSignal` the runtime uses to
+    /// tell every daemon it spawns to shut down.
+    ///
+    /// `Client` only keeps a `Source`, not a `Drain`: a fresh `Drain` is
+    /// minted for each daemon `watch` spawns, so holding a `Client` alive
+    /// never blocks `DrainSignal::drain` from observing that every daemon
+    /// has exited.
+    pub fn new(
+        service: DestinationClient<S>,
+        recover: R,
+        context_token: String,
+    ) -> (Self, DrainSignal) {
+        let (signal, drain) = self::drain::channel();
+        let client = Self {
+            service,
+            recover,
+            context_token,
+            drain,
+        };
+        (client, signal)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -87,12 +119,17 @@ where
             self.recover.clone(),
             tx,
             stream,
+            self.drain.subscribe(),
         ));
 
         Ok(rx)
     }
 
     /// Processes an initialized stream/watch, recovering as permitted.
+    ///
+    /// Exits as soon as either every receiver is dropped or `drain` is
+    /// signaled, so the proxy can bound its shutdown time instead of
+    /// waiting on the upstream stream or a backoff loop.
     async fn daemon(
         addr: SocketAddr,
         mut service: DestinationClient<S>,
@@ -100,16 +137,25 @@ where
         mut recover: R,
         mut tx: watch::Sender<Strategy>,
         mut responses: grpc::codec::Streaming<api::StrategyResponse>,
+        drain: Drain,
     ) {
         loop {
-            match Self::broadcast(addr, &mut tx, &mut responses).await {
-                Ok(()) => {
+            match Self::broadcast(addr, &mut tx, &mut responses, drain.clone()).await {
+                Ok(Terminated::Closed) => {
                     trace!("Shutting down; all receivers dropped");
                     return;
                 }
+                Ok(Terminated::Drained) => {
+                    trace!("Shutting down; drained");
+                    return;
+                }
                 Err(status) => {
                     futures::select_biased! {
                         () = tx.closed().fuse() => { return; }
+                        () = drain.clone().signaled().fuse() => {
+                            trace!("Shutting down; drained");
+                            return;
+                        }
                         res = Self::recover(addr, &mut service, req.clone(), &mut recover, status).fuse() => {
                             match res {
                                 Err(error) => {
@@ -174,16 +220,20 @@ where
     /// Publishes updates from `responses` to `tx` until either close.
     ///
     /// An error is returned if the `responses` stream terminates. Success is
-    /// returned if `tx` is closed.
+    /// returned if `tx` is closed or `drain` is signaled.
     async fn broadcast(
         addr: SocketAddr,
         tx: &mut watch::Sender<Strategy>,
         responses: &mut grpc::codec::Streaming<api::StrategyResponse>,
-    ) -> Result<(), grpc::Status> {
+        mut drain: Drain,
+    ) -> Result<Terminated, grpc::Status> {
         loop {
             futures::select_biased! {
                 () = tx.closed().fuse() => {
-                    return Ok(());
+                    return Ok(Terminated::Closed);
+                }
+                () = drain.clone().signaled().fuse() => {
+                    return Ok(Terminated::Drained);
                 }
                 res = responses.try_next().fuse() => {
                     match res? {
@@ -200,6 +250,14 @@ where
     }
 }
 
+/// Why a daemon's `broadcast` loop stopped publishing updates.
+enum Terminated {
+    /// Every receiver for this watch was dropped.
+    Closed,
+    /// A drain was signaled.
+    Drained,
+}
+
 impl Strategy {
     fn new(
         addr: SocketAddr,